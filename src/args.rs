@@ -0,0 +1,206 @@
+//! Command line argument parsing for `lupo`.
+
+use std::path::PathBuf;
+
+use clap::{App, Arg, SubCommand as ClapSubCommand};
+
+use lupo::Kind as BackendKind;
+
+use crate::ui::ColorChoice;
+
+/// Fully parsed, validated command line options.
+pub struct Opts {
+    pub directory: Option<PathBuf>,
+    pub quiet: bool,
+    pub verbose: usize,
+    pub ts: Option<stderrlog::Timestamp>,
+    pub color: ColorChoice,
+    pub format: Format,
+    pub blackbox: bool,
+    pub backend: Option<BackendKind>,
+    pub subcmd: SubCommand,
+}
+
+/// The `--format` flag: how `trades` and `port` render their rows.
+///
+/// `Plain` goes through [`crate::ui::Ui`] (and honors `--color`); `Json` and
+/// `Csv` serialize the underlying records directly and ignore color, since
+/// they're meant to be piped into other tools.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Plain,
+    Json,
+    Csv,
+}
+
+impl Format {
+    fn from_str(s: &str) -> Option<Format> {
+        match s {
+            "plain" => Some(Format::Plain),
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// The subcommand requested by the user, along with its own flags.
+pub enum SubCommand {
+    Init { force: bool },
+    Check { fix: bool, keep_going: bool },
+    Trades { name_substring: Option<String> },
+    Port {},
+}
+
+impl SubCommand {
+    /// The subcommand's name, as typed on the command line. Used by the
+    /// blackbox log to identify what was run.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SubCommand::Init { .. } => "init",
+            SubCommand::Check { .. } => "check",
+            SubCommand::Trades { .. } => "trades",
+            SubCommand::Port {} => "port",
+        }
+    }
+}
+
+pub fn parse_args() -> Opts {
+    let matches = App::new("lupo")
+        .about("A small command line trade and portfolio tracker.")
+        .arg(
+            Arg::with_name("directory")
+                .short("d")
+                .long("directory")
+                .takes_value(true)
+                .global(true)
+                .help("The trade store directory (defaults to ~/.lupo)."),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .global(true)
+                .help("Suppress all log output."),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .short("v")
+                .long("verbose")
+                .multiple(true)
+                .global(true)
+                .help("Increase log verbosity. May be repeated."),
+        )
+        .arg(
+            Arg::with_name("timestamp")
+                .long("timestamp")
+                .global(true)
+                .help("Include a timestamp with log output."),
+        )
+        .arg(
+            Arg::with_name("color")
+                .long("color")
+                .takes_value(true)
+                .possible_values(&["auto", "always", "never"])
+                .default_value("auto")
+                .global(true)
+                .help("Control whether output is colorized."),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["plain", "json", "csv"])
+                .default_value("plain")
+                .global(true)
+                .help("Output format for `trades` and `port` (plain, json or csv)."),
+        )
+        .arg(
+            Arg::with_name("no-blackbox")
+                .long("no-blackbox")
+                .global(true)
+                .help("Don't record this invocation in the store's blackbox log."),
+        )
+        .arg(
+            Arg::with_name("backend")
+                .long("backend")
+                .takes_value(true)
+                .possible_values(&["file"])
+                .global(true)
+                .help(
+                    "Storage backend to use. Defaults to whatever `init` recorded \
+                     for this store.",
+                ),
+        )
+        .subcommand(
+            ClapSubCommand::with_name("init")
+                .about("Create a new, empty trade store.")
+                .arg(
+                    Arg::with_name("force")
+                        .long("force")
+                        .help("Reinitialize the store even if one already exists."),
+                ),
+        )
+        .subcommand(
+            ClapSubCommand::with_name("check")
+                .about("Verify the trade store.")
+                .arg(
+                    Arg::with_name("fix")
+                        .long("fix")
+                        .help("Apply suggested fixes for recoverable problems."),
+                )
+                .arg(Arg::with_name("keep-going").long("keep-going").help(
+                    "Report every malformed trade instead of stopping at the first one.",
+                )),
+        )
+        .subcommand(
+            ClapSubCommand::with_name("trades")
+                .about("List recorded trades.")
+                .arg(Arg::with_name("name_substring").index(1)),
+        )
+        .subcommand(ClapSubCommand::with_name("port").about("Show the current portfolio."))
+        .get_matches();
+
+    let subcmd = match matches.subcommand() {
+        ("init", Some(m)) => SubCommand::Init {
+            force: m.is_present("force"),
+        },
+        ("check", Some(m)) => SubCommand::Check {
+            fix: m.is_present("fix"),
+            keep_going: m.is_present("keep-going"),
+        },
+        ("trades", Some(m)) => SubCommand::Trades {
+            name_substring: m.value_of("name_substring").map(|s| s.to_string()),
+        },
+        ("port", Some(_)) | _ => SubCommand::Port {},
+    };
+
+    Opts {
+        directory: Some(
+            matches
+                .value_of("directory")
+                .map(PathBuf::from)
+                .unwrap_or_else(default_home_dir),
+        ),
+        quiet: matches.is_present("quiet"),
+        verbose: matches.occurrences_of("verbose") as usize,
+        ts: if matches.is_present("timestamp") {
+            Some(stderrlog::Timestamp::Second)
+        } else {
+            None
+        },
+        color: ColorChoice::from_str(matches.value_of("color").unwrap()).unwrap(),
+        format: Format::from_str(matches.value_of("format").unwrap()).unwrap(),
+        blackbox: !matches.is_present("no-blackbox"),
+        backend: matches
+            .value_of("backend")
+            .map(|s| BackendKind::from_str(s).expect("validated by possible_values")),
+        subcmd,
+    }
+}
+
+fn default_home_dir() -> PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".lupo")
+}
@@ -0,0 +1,114 @@
+//! The storage extension point. Following the approach the `forge` tool
+//! took for its own storage layer ("a backend implements the `Backend`
+//! trait, which allows third-party backends"), `lupo` talks to a trade
+//! store only through this trait. The command layer in `main.rs` never
+//! names the concrete file-backed [`crate::Store`] directly; it opens
+//! whichever `Backend` the store's marker file (or an explicit `--backend`
+//! flag) selects.
+
+use std::fs;
+use std::path::Path;
+
+use crate::errors::*;
+use crate::{CheckReport, Position, Store, Trade};
+
+const BACKEND_MARKER_FILE: &str = ".backend";
+
+/// The storage engine backing a trade store.
+pub trait Backend {
+    /// Create a new, empty store rooted at `home_dir`.
+    fn new(home_dir: &Path, force: bool) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Open an existing store rooted at `home_dir`.
+    fn open(home_dir: &Path) -> Result<Self>
+    where
+        Self: Sized;
+
+    fn home_dir(&self) -> &Path;
+
+    fn check(&self, keep_going: bool) -> Result<CheckReport>;
+
+    /// Apply suggested fixes from a prior `check`. Backends that can't
+    /// rewrite their own records in place may leave this unimplemented.
+    fn fix(&self, _report: &CheckReport) -> Result<usize> {
+        Err("this backend does not support check --fix".into())
+    }
+
+    fn trades(&self, name_substring: Option<String>) -> Result<Vec<Trade>>;
+
+    fn port(&self) -> Result<Vec<Position>>;
+}
+
+/// Which [`Backend`] implementation a store uses. Recorded in the store's
+/// marker file by [`new_backend`] and consulted by [`open_backend`] so `lupo` doesn't need
+/// `--backend` repeated on every invocation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+    File,
+}
+
+impl Kind {
+    pub fn from_str(s: &str) -> Option<Kind> {
+        match s {
+            "file" => Some(Kind::File),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Kind::File => "file",
+        }
+    }
+
+    /// The backend kind recorded in `home_dir`'s marker file, written the
+    /// last time [`new_backend`] initialized it there.
+    ///
+    /// A missing marker file means a store created before backends existed,
+    /// so it's treated as a legacy [`Kind::File`] store rather than an
+    /// error.
+    pub fn detect(home_dir: &Path) -> Result<Kind> {
+        let marker = home_dir.join(BACKEND_MARKER_FILE);
+        let contents = match fs::read_to_string(&marker) {
+            Ok(contents) => contents,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Kind::File),
+            Err(e) => {
+                return Err(e).chain_err(|| format!("could not read {}", marker.display()))
+            }
+        };
+        Kind::from_str(contents.trim())
+            .ok_or_else(|| format!("unknown backend {:?} in {}", contents.trim(), marker.display()).into())
+    }
+
+    fn write_marker(self, home_dir: &Path) -> Result<()> {
+        fs::write(home_dir.join(BACKEND_MARKER_FILE), self.as_str())
+            .chain_err(|| format!("could not write backend marker in {}", home_dir.display()))
+    }
+}
+
+/// Initialize a fresh store of the given kind, recording the choice in its
+/// marker file so later `open`s know which `Backend` to use.
+pub fn new_backend(kind: Kind, home_dir: &Path, force: bool) -> Result<Box<dyn Backend>> {
+    match kind {
+        Kind::File => {
+            let store = Store::new(home_dir, force)?;
+            kind.write_marker(home_dir)?;
+            Ok(Box::new(store))
+        }
+    }
+}
+
+/// Open an existing store. `explicit` overrides the backend recorded in the
+/// store's marker file, for the `--backend` flag; otherwise the marker
+/// written by `new_backend` decides.
+pub fn open_backend(home_dir: &Path, explicit: Option<Kind>) -> Result<Box<dyn Backend>> {
+    let kind = match explicit {
+        Some(kind) => kind,
+        None => Kind::detect(home_dir)?,
+    };
+    match kind {
+        Kind::File => Ok(Box::new(Store::open(home_dir)?)),
+    }
+}
@@ -0,0 +1,69 @@
+//! A forensic audit trail of every invocation, modeled on `rhg`'s `blackbox`
+//! extension: one line per run, appended to a rotating log file under the
+//! store's home directory, recording what was run, when, and how it ended.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const BLACKBOX_FILE: &str = "blackbox.log";
+/// Once the log crosses this size, it is rotated out to `blackbox.log.old`.
+const MAX_BYTES: u64 = 1024 * 1024;
+
+/// One recorded invocation.
+pub struct Entry {
+    pub timestamp: String,
+    pub subcommand: String,
+    pub args: Vec<String>,
+    pub exit_status: i32,
+    pub duration: Duration,
+}
+
+impl Entry {
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{:?}\texit={}\t{}ms",
+            self.timestamp,
+            self.subcommand,
+            self.args,
+            self.exit_status,
+            self.duration.as_millis()
+        )
+    }
+}
+
+/// A handle to the blackbox log for a single store.
+pub struct BlackBox {
+    path: PathBuf,
+}
+
+impl BlackBox {
+    pub fn new(home_dir: &Path) -> BlackBox {
+        BlackBox {
+            path: home_dir.join(BLACKBOX_FILE),
+        }
+    }
+
+    /// Append `entry`, rotating the log first if it has grown too large.
+    pub fn record(&self, entry: &Entry) -> io::Result<()> {
+        self.rotate_if_needed()?;
+        let mut f = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(f, "{}", entry.to_line())
+    }
+
+    fn rotate_if_needed(&self) -> io::Result<()> {
+        let len = match fs::metadata(&self.path) {
+            Ok(m) => m.len(),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        if len >= MAX_BYTES {
+            fs::rename(&self.path, self.path.with_extension("log.old"))?;
+        }
+        Ok(())
+    }
+}
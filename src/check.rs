@@ -0,0 +1,124 @@
+//! Types produced by [`crate::Store::check`], modeled on `rustfix`'s
+//! suggestion-and-apply model: a diagnostic optionally carries a
+//! [`Suggestion`] describing exactly which bytes of the trade log would fix
+//! it, so `lupo check --fix` can apply it mechanically.
+
+use std::ops::Range;
+
+/// The result of verifying a trade log.
+#[derive(Debug)]
+pub struct CheckReport {
+    pub trades_ok: usize,
+    pub stocks_ok: usize,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl CheckReport {
+    /// Whether any diagnostic represents a hard failure rather than a
+    /// recoverable suggestion. Under `--keep-going`, the caller uses this to
+    /// decide whether to exit non-zero after printing the full report.
+    pub fn has_failures(&self) -> bool {
+        self.diagnostics.iter().any(|d| d.suggestion.is_none())
+    }
+}
+
+/// A single problem found while checking the trade log.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub suggestion: Option<Suggestion>,
+}
+
+/// A mechanical fix for a [`Diagnostic`]: replace the bytes at `span` (a
+/// byte range into the trade log file) with `replacement`.
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub span: Range<usize>,
+    pub replacement: Vec<u8>,
+}
+
+/// Apply `suggestions` to `contents`, skipping any suggestion whose span
+/// overlaps one already applied. Suggestions are sorted ascending by start
+/// offset and then applied front-to-back, copying each untouched gap and
+/// each replacement into a fresh buffer as we go — the original `contents`
+/// is never mutated in place, so there's no need to worry about earlier
+/// edits shifting the byte offsets later suggestions were computed against.
+///
+/// Returns the patched bytes and the number of suggestions that were
+/// actually applied.
+pub fn apply_suggestions(contents: &[u8], suggestions: &[Suggestion]) -> (Vec<u8>, usize) {
+    let mut ordered: Vec<&Suggestion> = suggestions.iter().collect();
+    ordered.sort_by_key(|s| s.span.start);
+
+    let mut accepted: Vec<&Suggestion> = vec![];
+    let mut last_end = 0;
+    for s in ordered {
+        if s.span.start < last_end {
+            continue; // overlaps a suggestion we already accepted
+        }
+        last_end = s.span.end;
+        accepted.push(s);
+    }
+
+    let mut patched = Vec::with_capacity(contents.len());
+    let mut cursor = 0;
+    for s in &accepted {
+        patched.extend_from_slice(&contents[cursor..s.span.start]);
+        patched.extend_from_slice(&s.replacement);
+        cursor = s.span.end;
+    }
+    patched.extend_from_slice(&contents[cursor..]);
+    (patched, accepted.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suggestion(span: Range<usize>, replacement: &str) -> Suggestion {
+        Suggestion {
+            span,
+            replacement: replacement.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn applies_non_overlapping_suggestions() {
+        let contents = b"2024-01-01,aapl,buy,1,1.00\n";
+        let (patched, applied) = apply_suggestions(
+            contents,
+            &[suggestion(11..15, "AAPL"), suggestion(16..19, "BUY")],
+        );
+        assert_eq!(applied, 2);
+        assert_eq!(patched, b"2024-01-01,AAPL,BUY,1,1.00\n");
+    }
+
+    #[test]
+    fn skips_suggestion_overlapping_one_already_accepted() {
+        let contents = b"aaaa";
+        // The second suggestion starts inside the first's span, so it
+        // should be dropped rather than applied on top of stale offsets.
+        let (patched, applied) =
+            apply_suggestions(contents, &[suggestion(0..3, "XX"), suggestion(1..4, "YY")]);
+        assert_eq!(applied, 1);
+        assert_eq!(patched, b"XXa");
+    }
+
+    #[test]
+    fn accepts_adjacent_non_overlapping_suggestions() {
+        let contents = b"abcdef";
+        let (patched, applied) =
+            apply_suggestions(contents, &[suggestion(0..2, "XX"), suggestion(2..4, "YY")]);
+        assert_eq!(applied, 2);
+        assert_eq!(patched, b"XXYYef");
+    }
+
+    #[test]
+    fn order_of_input_does_not_affect_which_suggestion_wins() {
+        let contents = b"aaaa";
+        let (patched, applied) =
+            apply_suggestions(contents, &[suggestion(1..4, "YY"), suggestion(0..3, "XX")]);
+        assert_eq!(applied, 1);
+        assert_eq!(patched, b"XXa");
+    }
+}
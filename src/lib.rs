@@ -0,0 +1,399 @@
+//! `lupo` is a small, file-backed trade and portfolio tracker.
+//!
+//! The crate exposes [`Store`], which owns a directory of flat trade-log
+//! files and knows how to append to, verify, and summarize them. The `lupo`
+//! binary (see `src/main.rs`) is a thin command-line wrapper around it.
+
+#[macro_use]
+extern crate error_chain;
+
+mod backend;
+pub use backend::{new_backend, open_backend, Backend, Kind};
+
+mod check;
+pub use check::{apply_suggestions, CheckReport, Diagnostic, Suggestion};
+
+mod snapshot;
+
+pub mod errors {
+    error_chain! {
+        foreign_links {
+            Io(::std::io::Error);
+            ParseFloat(::std::num::ParseFloatError);
+            ParseInt(::std::num::ParseIntError);
+            Json(::serde_json::Error);
+            Csv(::csv::Error);
+        }
+    }
+}
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use errors::*;
+
+const TRADES_FILE: &str = "trades.log";
+
+/// A single buy or sell of a stock.
+#[derive(Clone, Debug, Serialize)]
+pub struct Trade {
+    pub date: String,
+    pub stock: String,
+    pub action: Action,
+    pub quantity: f64,
+    pub price: f64,
+}
+
+impl fmt::Display for Trade {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} @ {}",
+            self.date, self.action, self.quantity, self.stock, self.price
+        )
+    }
+}
+
+/// Whether a [`Trade`] added or removed shares.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Buy,
+    Sell,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Action::Buy => write!(f, "buy"),
+            Action::Sell => write!(f, "sell"),
+        }
+    }
+}
+
+/// A trade store: a directory on disk holding one append-only trade log.
+pub struct Store {
+    pub home_dir: PathBuf,
+}
+
+impl Store {
+    fn read_trades(&self) -> Result<Vec<Trade>> {
+        let contents = fs::read_to_string(self.home_dir.join(TRADES_FILE))
+            .chain_err(|| format!("could not read {}", self.home_dir.display()))?;
+        let mut trades = vec![];
+        for (_, line) in split_lines(&contents).filter(|(_, l)| !l.is_empty()) {
+            trades.push(parse_trade_line(line)?);
+        }
+        Ok(trades)
+    }
+}
+
+/// Split `contents` into `(byte_offset, line)` pairs, one per `\n`-delimited
+/// line, with a trailing `\r` stripped from each line so CRLF and LF logs
+/// parse identically.
+///
+/// `byte_offset` is the start of the line (including any stripped `\r`)
+/// within `contents`, which is what `check`'s [`Suggestion`] spans are
+/// computed against.
+fn split_lines(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut offset = 0;
+    contents.split('\n').map(move |line| {
+        let line_start = offset;
+        offset += line.len() + 1; // account for the '\n' split() consumed
+        (line_start, line.strip_suffix('\r').unwrap_or(line))
+    })
+}
+
+impl Backend for Store {
+    /// Create a new, empty store rooted at `home_dir`.
+    ///
+    /// If `home_dir` already looks like a store and `force` is `false`, this
+    /// returns an error rather than clobbering existing trades.
+    fn new(home_dir: &Path, force: bool) -> Result<Store> {
+        if home_dir.join(TRADES_FILE).exists() && !force {
+            return Err(format!(
+                "{} already contains a trade store (use --force to reinitialize)",
+                home_dir.display()
+            )
+            .into());
+        }
+        fs::create_dir_all(home_dir)
+            .chain_err(|| format!("could not create {}", home_dir.display()))?;
+        fs::write(home_dir.join(TRADES_FILE), "")
+            .chain_err(|| format!("could not initialize trade log in {}", home_dir.display()))?;
+        Ok(Store {
+            home_dir: home_dir.to_path_buf(),
+        })
+    }
+
+    /// Open an existing store rooted at `home_dir`.
+    fn open(home_dir: &Path) -> Result<Store> {
+        if !home_dir.join(TRADES_FILE).exists() {
+            return Err(format!("{} is not a lupo trade store", home_dir.display()).into());
+        }
+        Ok(Store {
+            home_dir: home_dir.to_path_buf(),
+        })
+    }
+
+    fn home_dir(&self) -> &Path {
+        &self.home_dir
+    }
+
+    /// Verify every trade in the log, returning counts of what processed
+    /// correctly plus a [`Diagnostic`] for each problem found. Diagnostics
+    /// for recoverable problems (like a non-uppercase stock symbol) carry a
+    /// [`Suggestion`] that `fix` can apply.
+    ///
+    /// By default, the first malformed trade aborts the whole check. With
+    /// `keep_going`, every malformed trade is instead recorded as a
+    /// diagnostic (with no suggestion) and checking continues over the rest
+    /// of the log, following the `no-fail-fast` pattern used by rustc's
+    /// bootstrap test runner. Those delayed failures are returned inside the
+    /// `Ok` report; it is up to the caller to turn them into a single error
+    /// (see [`CheckReport::has_failures`]).
+    fn check(&self, keep_going: bool) -> Result<CheckReport> {
+        let path = self.home_dir.join(TRADES_FILE);
+        let contents = fs::read_to_string(&path)
+            .chain_err(|| format!("could not read {}", path.display()))?;
+
+        let mut trades_ok = 0;
+        let mut stocks = std::collections::HashSet::new();
+        let mut diagnostics = vec![];
+        for (line_start, line) in split_lines(&contents) {
+            if line.is_empty() {
+                continue;
+            }
+            match parse_trade_line(line) {
+                Ok(t) => {
+                    trades_ok += 1;
+                    stocks.insert(t.stock.clone());
+                    if let Some(suggestion) = suggest_uppercase_symbol(line, line_start, &t) {
+                        diagnostics.push(Diagnostic {
+                            message: format!(
+                                "stock symbol {:?} should be uppercase",
+                                t.stock
+                            ),
+                            suggestion: Some(suggestion),
+                        });
+                    }
+                }
+                Err(e) => {
+                    if !keep_going {
+                        return Err(e).chain_err(|| format!("malformed trade record: {:?}", line));
+                    }
+                    diagnostics.push(Diagnostic {
+                        message: format!("{:?}: {}", line, e),
+                        suggestion: None,
+                    });
+                }
+            }
+        }
+        Ok(CheckReport {
+            trades_ok,
+            stocks_ok: stocks.len(),
+            diagnostics,
+        })
+    }
+
+    /// Apply every suggestion in `report` to the trade log, after copying
+    /// the current file to `trades.log.bak`. Suggestions whose byte spans
+    /// overlap one already applied are skipped. Returns the number applied.
+    fn fix(&self, report: &CheckReport) -> Result<usize> {
+        let path = self.home_dir.join(TRADES_FILE);
+        let contents = fs::read(&path)
+            .chain_err(|| format!("could not read {}", path.display()))?;
+        fs::copy(&path, path.with_extension("log.bak"))
+            .chain_err(|| format!("could not back up {}", path.display()))?;
+
+        let suggestions: Vec<Suggestion> = report
+            .diagnostics
+            .iter()
+            .filter_map(|d| d.suggestion.clone())
+            .collect();
+        let (patched, applied) = apply_suggestions(&contents, &suggestions);
+
+        fs::write(&path, patched)
+            .chain_err(|| format!("could not write {}", path.display()))?;
+        Ok(applied)
+    }
+
+    /// List all trades, optionally restricted to stocks whose symbol
+    /// contains `name_substring`.
+    fn trades(&self, name_substring: Option<String>) -> Result<Vec<Trade>> {
+        let trades = self.read_trades()?;
+        Ok(match name_substring {
+            None => trades,
+            Some(sub) => trades
+                .into_iter()
+                .filter(|t| t.stock.contains(sub.as_str()))
+                .collect(),
+        })
+    }
+
+    /// Summarize the current position in every stock ever traded.
+    ///
+    /// Rather than re-parsing the whole log, this loads the positions a
+    /// prior `port` snapshotted and replays only the trades appended since,
+    /// so repeated queries on a large store parse O(new trades) records
+    /// instead of O(all trades). The whole log is still read and its
+    /// snapshotted prefix hashed on every call to validate the snapshot.
+    fn port(&self) -> Result<Vec<Position>> {
+        let path = self.home_dir.join(TRADES_FILE);
+        let contents = fs::read(&path)
+            .chain_err(|| format!("could not read {}", path.display()))?;
+
+        let (consumed, mut by_stock) = snapshot::load(&self.home_dir, &contents);
+        let remainder = std::str::from_utf8(&contents[consumed..])
+            .chain_err(|| format!("{} is not valid UTF-8", path.display()))?;
+        for (_, line) in split_lines(remainder).filter(|(_, l)| !l.is_empty()) {
+            let t = parse_trade_line(line)?;
+            by_stock
+                .entry(t.stock.clone())
+                .or_insert_with(|| Position::new(t.stock.clone()))
+                .apply(&t);
+        }
+
+        snapshot::save(&self.home_dir, &contents, &by_stock);
+        Ok(by_stock.into_values().collect())
+    }
+}
+
+/// The net holding in a single stock, derived from every trade seen so far.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Position {
+    pub stock: String,
+    pub quantity: f64,
+    pub cost_basis: f64,
+    pub realized_gain: f64,
+}
+
+impl Position {
+    fn new(stock: String) -> Position {
+        Position {
+            stock,
+            quantity: 0.0,
+            cost_basis: 0.0,
+            realized_gain: 0.0,
+        }
+    }
+
+    /// Fold a single trade into this position, updating quantity, the
+    /// running average cost basis, and realized gain/loss on sells.
+    fn apply(&mut self, t: &Trade) {
+        match t.action {
+            Action::Buy => {
+                self.cost_basis += t.quantity * t.price;
+                self.quantity += t.quantity;
+            }
+            Action::Sell => {
+                let avg_cost = if self.quantity > 0.0 {
+                    self.cost_basis / self.quantity
+                } else {
+                    0.0
+                };
+                self.realized_gain += (t.price - avg_cost) * t.quantity;
+                self.cost_basis -= avg_cost * t.quantity;
+                self.quantity -= t.quantity;
+            }
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} shares (realized {:+.2})",
+            self.stock, self.quantity, self.realized_gain
+        )
+    }
+}
+
+/// If `t`'s stock symbol isn't already uppercase, suggest replacing the
+/// symbol field (at its byte span within the full file, starting at
+/// `line_start`) with its uppercased form.
+///
+/// The symbol field's offset is derived from the `,`-separated layout
+/// (`date,stock,action,quantity,price`) rather than searched for, since a
+/// substring search could match the symbol's text inside an earlier field
+/// (e.g. a date) and patch the wrong bytes.
+fn suggest_uppercase_symbol(line: &str, line_start: usize, t: &Trade) -> Option<Suggestion> {
+    let upper = t.stock.to_uppercase();
+    if upper == t.stock {
+        return None;
+    }
+    let field_start = line.find(',')? + 1;
+    let span = (line_start + field_start)..(line_start + field_start + t.stock.len());
+    Some(Suggestion {
+        span,
+        replacement: upper.into_bytes(),
+    })
+}
+
+fn parse_trade_line(line: &str) -> Result<Trade> {
+    let fields: Vec<&str> = line.split(',').collect();
+    if fields.len() != 5 {
+        return Err(format!("malformed trade record: {:?}", line).into());
+    }
+    let action = match fields[2] {
+        "buy" => Action::Buy,
+        "sell" => Action::Sell,
+        other => return Err(format!("unknown trade action: {:?}", other).into()),
+    };
+    Ok(Trade {
+        date: fields[0].to_string(),
+        stock: fields[1].to_string(),
+        action,
+        quantity: fields[3].parse()?,
+        price: fields[4].parse()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(action: Action, quantity: f64, price: f64) -> Trade {
+        Trade {
+            date: "2024-01-01".to_string(),
+            stock: "AAPL".to_string(),
+            action,
+            quantity,
+            price,
+        }
+    }
+
+    #[test]
+    fn buy_accumulates_cost_basis_without_touching_realized_gain() {
+        let mut p = Position::new("AAPL".to_string());
+        p.apply(&trade(Action::Buy, 10.0, 100.0));
+        p.apply(&trade(Action::Buy, 10.0, 200.0));
+        assert_eq!(p.quantity, 20.0);
+        assert_eq!(p.cost_basis, 3000.0);
+        assert_eq!(p.realized_gain, 0.0);
+    }
+
+    #[test]
+    fn sell_realizes_gain_against_average_cost_and_shrinks_cost_basis() {
+        let mut p = Position::new("AAPL".to_string());
+        p.apply(&trade(Action::Buy, 10.0, 100.0));
+        p.apply(&trade(Action::Buy, 10.0, 200.0));
+        // Average cost is (1000 + 2000) / 20 = 150/share.
+        p.apply(&trade(Action::Sell, 5.0, 180.0));
+        assert_eq!(p.quantity, 15.0);
+        assert_eq!(p.realized_gain, (180.0 - 150.0) * 5.0);
+        assert_eq!(p.cost_basis, 3000.0 - 150.0 * 5.0);
+    }
+
+    #[test]
+    fn selling_from_an_empty_position_does_not_divide_by_zero() {
+        let mut p = Position::new("AAPL".to_string());
+        p.apply(&trade(Action::Sell, 5.0, 50.0));
+        assert_eq!(p.quantity, -5.0);
+        assert_eq!(p.realized_gain, 50.0 * 5.0);
+    }
+}
@@ -1,8 +1,17 @@
+use std::io;
+use std::time::Instant;
+
 use log::error;
 
 mod args;
 use args::*;
 
+mod blackbox;
+use blackbox::{BlackBox, Entry};
+
+mod ui;
+use ui::Ui;
+
 use lupo::errors::*;
 use lupo::*;
 
@@ -23,7 +32,34 @@ fn reset_signal_pipe_handler() -> Result<()> {
 fn main() {
     reset_signal_pipe_handler().unwrap();
 
-    if let Err(ref e) = run() {
+    let opts = parse_args();
+    let home_dir = opts.directory.clone().unwrap();
+    let blackbox = opts.blackbox;
+    let subcommand = opts.subcmd.name();
+    let invocation_args: Vec<String> = std::env::args().skip(1).collect();
+
+    let start = Instant::now();
+    let result = run(opts);
+    let exit_status = if result.is_ok() { 0 } else { 1 };
+
+    if blackbox {
+        let epoch_secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let entry = Entry {
+            timestamp: epoch_secs.to_string(),
+            subcommand: subcommand.to_string(),
+            args: invocation_args,
+            exit_status,
+            duration: start.elapsed(),
+        };
+        if let Err(e) = BlackBox::new(&home_dir).record(&entry) {
+            error!("failed to write blackbox log: {}", e);
+        }
+    }
+
+    if let Err(ref e) = result {
         let mut s = e.to_string();
 
         for e in e.iter().skip(1) {
@@ -41,9 +77,7 @@ fn main() {
     }
 }
 
-fn run() -> Result<()> {
-    let opts = parse_args();
-
+fn run(opts: Opts) -> Result<()> {
     stderrlog::new()
         .module(module_path!())
         .show_level(false)
@@ -54,31 +88,95 @@ fn run() -> Result<()> {
         .unwrap();
 
     let home_dir = &opts.directory.unwrap();
+    let mut ui = Ui::new(opts.color);
 
     match opts.subcmd {
         SubCommand::Init { force } => {
-            let store = Store::new(home_dir, force)?;
-            println!("Data directory: {}", store.home_dir.to_string_lossy());
-            Ok(())
+            let store = new_backend(opts.backend.unwrap_or(Kind::File), home_dir, force)?;
+            ui.status(&format!("Data directory: {}", store.home_dir().to_string_lossy()))
+                .chain_err(|| "failed to write output")?;
         }
-        SubCommand::Check {} => {
-            let store = Store::open(home_dir)?;
-            let (ct, cs) = store.check()?;
-            println!("{} trades processed correctly.", ct);
-            println!("{} stocks processed correctly.", cs);
-            Ok(())
+        SubCommand::Check { fix, keep_going } => {
+            let store = open_backend(home_dir, opts.backend)?;
+            // `--fix` needs a full report to apply every recoverable
+            // suggestion, not just whatever ran before the first
+            // unrecoverable record, so it implies `--keep-going`.
+            let report = store.check(keep_going || fix)?;
+            if fix {
+                let applied = store.fix(&report)?;
+                ui.status(&format!(
+                    "Applied {} of {} suggested fixes (backup at trades.log.bak).",
+                    applied,
+                    report.diagnostics.iter().filter(|d| d.suggestion.is_some()).count()
+                ))
+            } else {
+                report.diagnostics.iter().try_for_each(|d| match &d.suggestion {
+                    Some(s) => ui.error(&format!(
+                        "{} (suggested fix: replace bytes {}..{} with {:?})",
+                        d.message,
+                        s.span.start,
+                        s.span.end,
+                        String::from_utf8_lossy(&s.replacement)
+                    )),
+                    None => ui.error(&d.message),
+                })
+            }
+            .chain_err(|| "failed to write output")?;
+            ui.status(&format!("{} trades processed correctly.", report.trades_ok))
+                .and_then(|_| {
+                    ui.status(&format!("{} stocks processed correctly.", report.stocks_ok))
+                })
+                .chain_err(|| "failed to write output")?;
+            if report.has_failures() {
+                return Err(format!(
+                    "{} malformed trade record(s) found; rerun without --keep-going to stop at \
+                     the first one, or with --fix to repair what's recoverable",
+                    report.diagnostics.iter().filter(|d| d.suggestion.is_none()).count()
+                )
+                .into());
+            }
         }
         SubCommand::Trades { name_substring } => {
-            let store = Store::open(home_dir)?;
+            let store = open_backend(home_dir, opts.backend)?;
             let trades = store.trades(name_substring)?;
-            trades.iter().for_each(|t| println!("{}", t));
-            Ok(())
+            match opts.format {
+                Format::Plain => ui
+                    .header("Trades")
+                    .and_then(|_| trades.iter().try_for_each(|t| ui.trade(t)))
+                    .chain_err(|| "failed to write output")?,
+                Format::Json => write_json(&trades)?,
+                Format::Csv => write_csv(&trades)?,
+            }
         }
         SubCommand::Port {} => {
-            let store = Store::open(home_dir)?;
-            let port_lines = store.port()?;
-            port_lines.iter().for_each(|l| println!("{:}", l));
-            Ok(())
+            let store = open_backend(home_dir, opts.backend)?;
+            let positions = store.port()?;
+            match opts.format {
+                Format::Plain => ui
+                    .header("Portfolio")
+                    .and_then(|_| positions.iter().try_for_each(|p| ui.position(p)))
+                    .chain_err(|| "failed to write output")?,
+                Format::Json => write_json(&positions)?,
+                Format::Csv => write_csv(&positions)?,
+            }
         }
+    };
+    ui.flush().chain_err(|| "failed to flush output")
+}
+
+/// Serialize `rows` as a single JSON array to stdout.
+fn write_json<T: serde::Serialize>(rows: &[T]) -> Result<()> {
+    serde_json::to_writer(io::stdout(), rows)?;
+    println!();
+    Ok(())
+}
+
+/// Serialize `rows` as a header followed by one CSV record per row.
+fn write_csv<T: serde::Serialize>(rows: &[T]) -> Result<()> {
+    let mut wtr = csv::Writer::from_writer(io::stdout());
+    for row in rows {
+        wtr.serialize(row)?;
     }
+    wtr.flush()?;
+    Ok(())
 }
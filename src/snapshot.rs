@@ -0,0 +1,152 @@
+//! A materialized cache of `port`'s aggregation, so repeated queries on a
+//! large trade log don't re-scan it from the start every time. Modeled on
+//! the optimization Mercurial uses for relevant-marker discovery: make one
+//! streaming pass over the log instead of rescanning it per stock, and
+//! remember how far that pass got so later runs only replay what's new.
+//!
+//! The snapshot records the byte length of the log it was built from and a
+//! hash of that prefix. If the log has grown, only the appended bytes are
+//! replayed on top of the saved positions. If the hash no longer matches —
+//! because an earlier record was edited, e.g. by `check --fix` — the
+//! snapshot is discarded and the next `port` rebuilds it from scratch.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Position;
+
+const SNAPSHOT_FILE: &str = "port.snapshot";
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    len: u64,
+    hash: u64,
+    positions: Vec<Position>,
+}
+
+fn hash_prefix(contents: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Load a snapshot for `contents`, if one exists and its recorded prefix
+/// still matches the start of `contents`. Returns the number of leading
+/// bytes it already accounts for, and the positions built from them.
+pub fn load(home_dir: &Path, contents: &[u8]) -> (usize, BTreeMap<String, Position>) {
+    let path = home_dir.join(SNAPSHOT_FILE);
+    let snapshot: Snapshot = match fs::read(&path) {
+        Ok(bytes) => match serde_json::from_slice(&bytes) {
+            Ok(s) => s,
+            Err(_) => return (0, BTreeMap::new()),
+        },
+        Err(_) => return (0, BTreeMap::new()),
+    };
+
+    let len = snapshot.len as usize;
+    if len > contents.len() || hash_prefix(&contents[..len]) != snapshot.hash {
+        return (0, BTreeMap::new());
+    }
+    let positions = snapshot
+        .positions
+        .into_iter()
+        .map(|p| (p.stock.clone(), p))
+        .collect();
+    (len, positions)
+}
+
+/// Save a snapshot covering all of `contents`, so the next `port` only has
+/// to replay whatever is appended after it.
+pub fn save(home_dir: &Path, contents: &[u8], positions: &BTreeMap<String, Position>) {
+    let snapshot = Snapshot {
+        len: contents.len() as u64,
+        hash: hash_prefix(contents),
+        positions: positions.values().cloned().collect(),
+    };
+    // Best-effort: a failure to cache shouldn't fail the command itself.
+    if let Ok(bytes) = serde_json::to_vec(&snapshot) {
+        let _ = fs::write(home_dir.join(SNAPSHOT_FILE), bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_home(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lupo-snapshot-test-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn position(stock: &str, quantity: f64) -> Position {
+        Position {
+            stock: stock.to_string(),
+            quantity,
+            cost_basis: quantity * 10.0,
+            realized_gain: 0.0,
+        }
+    }
+
+    #[test]
+    fn load_returns_empty_when_no_snapshot_exists() {
+        let home = temp_home("missing");
+        let (consumed, positions) = load(&home, b"whatever");
+        assert_eq!(consumed, 0);
+        assert!(positions.is_empty());
+        fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn save_then_load_round_trips_on_an_unchanged_prefix() {
+        let home = temp_home("roundtrip");
+        let contents = b"2024-01-01,AAPL,buy,1,10\n";
+        let mut positions = BTreeMap::new();
+        positions.insert("AAPL".to_string(), position("AAPL", 1.0));
+        save(&home, contents, &positions);
+
+        let (consumed, loaded) = load(&home, contents);
+        assert_eq!(consumed, contents.len());
+        assert_eq!(loaded.get("AAPL").unwrap().quantity, 1.0);
+        fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn load_replays_only_appended_bytes_when_prefix_still_matches() {
+        let home = temp_home("append");
+        let original = b"2024-01-01,AAPL,buy,1,10\n";
+        let mut positions = BTreeMap::new();
+        positions.insert("AAPL".to_string(), position("AAPL", 1.0));
+        save(&home, original, &positions);
+
+        let mut grown = original.to_vec();
+        grown.extend_from_slice(b"2024-01-02,MSFT,buy,2,20\n");
+        let (consumed, loaded) = load(&home, &grown);
+        assert_eq!(consumed, original.len());
+        assert_eq!(loaded.len(), 1);
+        assert!(loaded.contains_key("AAPL"));
+        fs::remove_dir_all(&home).unwrap();
+    }
+
+    #[test]
+    fn load_discards_snapshot_when_the_recorded_prefix_has_changed() {
+        let home = temp_home("edited");
+        let original = b"2024-01-01,aapl,buy,1,10\n";
+        let mut positions = BTreeMap::new();
+        positions.insert("aapl".to_string(), position("aapl", 1.0));
+        save(&home, original, &positions);
+
+        // Simulate `check --fix` editing an earlier record in place.
+        let edited = b"2024-01-01,AAPL,buy,1,10\n";
+        let (consumed, loaded) = load(&home, edited);
+        assert_eq!(consumed, 0);
+        assert!(loaded.is_empty());
+        fs::remove_dir_all(&home).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+//! A small buffered, color-aware output layer, modeled on Mercurial's `rhg`
+//! `ui`/color split: commands write through `Ui` instead of calling
+//! `println!` directly, so color is decided in one place instead of being
+//! scattered across subcommands. Only the `plain` `--format` goes through
+//! `Ui`; `json` and `csv` output is serialized straight to stdout by
+//! `main.rs`.
+
+use std::io::{self, BufWriter, IsTerminal, Write};
+
+use lupo::{Position, Trade};
+
+/// The `--color` flag: whether to colorize output.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn from_str(s: &str) -> Option<ColorChoice> {
+        match s {
+            "auto" => Some(ColorChoice::Auto),
+            "always" => Some(ColorChoice::Always),
+            "never" => Some(ColorChoice::Never),
+            _ => None,
+        }
+    }
+
+    fn enabled(self, stdout_is_tty: bool) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => stdout_is_tty,
+        }
+    }
+}
+
+const BOLD: &str = "\x1b[1m";
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Buffered stdout/stderr handles plus the resolved color mode. All
+/// human-readable output of the subcommands goes through here rather than
+/// through `println!`/`eprintln!`; `json` and `csv` output does not.
+pub struct Ui {
+    stdout: BufWriter<io::Stdout>,
+    stderr: BufWriter<io::Stderr>,
+    color: bool,
+}
+
+impl Ui {
+    pub fn new(choice: ColorChoice) -> Ui {
+        let color = choice.enabled(io::stdout().is_terminal());
+        Ui {
+            stdout: BufWriter::new(io::stdout()),
+            stderr: BufWriter::new(io::stderr()),
+            color,
+        }
+    }
+
+    fn paint(&self, code: &str, s: &str) -> String {
+        if self.color {
+            format!("{}{}{}", code, s, RESET)
+        } else {
+            s.to_string()
+        }
+    }
+
+    pub fn header(&mut self, s: &str) -> io::Result<()> {
+        writeln!(self.stdout, "{}", self.paint(BOLD, s))
+    }
+
+    pub fn trade(&mut self, t: &Trade) -> io::Result<()> {
+        writeln!(self.stdout, "{}", t)
+    }
+
+    pub fn position(&mut self, p: &Position) -> io::Result<()> {
+        let code = if p.realized_gain > 0.0 {
+            GREEN
+        } else if p.realized_gain < 0.0 {
+            RED
+        } else {
+            ""
+        };
+        writeln!(self.stdout, "{}", self.paint(code, &p.to_string()))
+    }
+
+    pub fn status(&mut self, s: &str) -> io::Result<()> {
+        writeln!(self.stdout, "{}", s)
+    }
+
+    pub fn error(&mut self, s: &str) -> io::Result<()> {
+        writeln!(self.stderr, "{}", self.paint(RED, s))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()?;
+        self.stderr.flush()?;
+        Ok(())
+    }
+}